@@ -1,14 +1,40 @@
 use dbg_breakpoint::breakpoint;
+use dbg_breakpoint::install_panic_hook;
 use dbg_breakpoint::is_debugger_present;
 
 fn main() {
+    install_panic_hook();
+
     let is_debugger_present = is_debugger_present();
     println!("Is debugger present: {is_debugger_present:?}");
 
-    #[cfg(all(target_os = "windows", target_pointer_width = "64"))]
+    #[cfg(target_os = "windows")]
     {
         let is_debugger_present = dbg_breakpoint::breakpoint_if_debugging_seh();
-        println!("Windows 64-bit SEH: is debugger present: {is_debugger_present:?}");
+        println!("Windows SEH: is debugger present: {is_debugger_present:?}");
+
+        #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+        {
+            let signals = dbg_breakpoint::is_debugger_present_thorough();
+            println!("Windows thorough check: is debugger present: {}, signals: {signals:?}", signals.detected());
+        }
+    }
+
+    #[cfg(all(unix, any(target_arch = "x86_64", target_arch = "aarch64")))]
+    {
+        let is_debugger_present = dbg_breakpoint::breakpoint_if_debugging_guarded();
+        println!("Unix signal-guarded: is debugger present: {is_debugger_present:?}");
+
+        // BreakpointGuard is what breakpoint_if_debugging_guarded() uses under the
+        // hood; exercise it directly too, including a nested guard.
+        let outer = dbg_breakpoint::BreakpointGuard::new();
+        breakpoint!();
+        println!("Outer guard stepped over its trap: {}", outer.stepped_over());
+        {
+            let inner = dbg_breakpoint::BreakpointGuard::new();
+            breakpoint!();
+            println!("Inner guard stepped over its trap: {}", inner.stepped_over());
+        }
     }
 
     println!("Now the process will crash if the debugger is not attcahed");
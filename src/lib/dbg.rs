@@ -0,0 +1,287 @@
+//! Debugging aids.
+
+use crate::DebuggerPresence;
+
+/// Error detecting debugger presence.
+#[derive(Copy, Clone, Debug)]
+pub enum DebuggerPresenceError {
+    /// The functionality is not available.
+    #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "freebsd",)))]
+    NotImplemented,
+    /// The detection logic failed to determine
+    /// the debugger presence. It may or may not be
+    /// attached.
+    #[cfg(any(target_os = "macos", target_os = "freebsd", target_os = "linux"))]
+    DetectionFailed,
+}
+
+/// Which individual signals fired during [`is_debugger_present_thorough`].
+///
+/// `kernel32!IsDebuggerPresent` only reports the `BeingDebugged` byte in
+/// the PEB, which a target (or an anti-debug-aware debugger) can zero out
+/// from under it. This cross-checks a few more sources so clearing one of
+/// them isn't enough to hide from detection.
+///
+/// Only available on `x86_64`/`x86`: the PEB walk below reaches the TEB
+/// through a segment register (`gs:[0x60]`/`fs:[0x30]`), which has no
+/// equivalent on `aarch64` (the TEB there is kept in `x18`).
+#[cfg(all(target_os = "windows", any(target_arch = "x86_64", target_arch = "x86")))]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct DebuggerPresenceSignals {
+    /// `BeingDebugged` read directly from the PEB, rather than through
+    /// `IsDebuggerPresent`.
+    pub being_debugged: bool,
+    /// `NtGlobalFlag` in the PEB has the heap debug bits set
+    /// (`FLG_HEAP_ENABLE_TAIL_CHECK | FLG_HEAP_ENABLE_FREE_CHECK |
+    /// FLG_HEAP_VALIDATE_PARAMETERS`), which the loader sets for a process
+    /// started under a debugger.
+    pub heap_debug_flags: bool,
+    /// `kernel32!CheckRemoteDebuggerPresent` reports a debugger attached
+    /// to the current process.
+    pub check_remote_debugger_present: bool,
+}
+
+#[cfg(all(target_os = "windows", any(target_arch = "x86_64", target_arch = "x86")))]
+impl DebuggerPresenceSignals {
+    /// Whether any of the individual signals fired.
+    pub fn detected(&self) -> bool {
+        self.being_debugged || self.heap_debug_flags || self.check_remote_debugger_present
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod os {
+    use super::{DebuggerPresence, DebuggerPresenceError};
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    use super::DebuggerPresenceSignals;
+    use std::ffi::c_void;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn IsDebuggerPresent() -> i32;
+        fn CheckRemoteDebuggerPresent(process: *mut c_void, present: *mut i32) -> i32;
+        fn GetCurrentProcess() -> *mut c_void;
+    }
+
+    pub(super) fn is_debugger_present() -> Result<DebuggerPresence, DebuggerPresenceError> {
+        // SAFETY: No state is shared between threads. The call reads
+        // a field from the Thread Environment Block using the OS API
+        // as required by the documentation.
+        if unsafe { IsDebuggerPresent() } != 0 {
+            Ok(DebuggerPresence::Detected)
+        } else {
+            Ok(DebuggerPresence::NotDetected)
+        }
+    }
+
+    // Offset of `BeingDebugged` in the PEB, stable since Windows NT and
+    // documented (if unofficially) for both architectures.
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    const BEING_DEBUGGED_OFFSET: usize = 0x02;
+
+    #[cfg(target_arch = "x86_64")]
+    const NT_GLOBAL_FLAG_OFFSET: usize = 0xbc;
+    #[cfg(target_arch = "x86")]
+    const NT_GLOBAL_FLAG_OFFSET: usize = 0x68;
+
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    const FLG_HEAP_ENABLE_TAIL_CHECK: u32 = 0x10;
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    const FLG_HEAP_ENABLE_FREE_CHECK: u32 = 0x20;
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    const FLG_HEAP_VALIDATE_PARAMETERS: u32 = 0x40;
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    const HEAP_DEBUG_FLAGS: u32 =
+        FLG_HEAP_ENABLE_TAIL_CHECK | FLG_HEAP_ENABLE_FREE_CHECK | FLG_HEAP_VALIDATE_PARAMETERS;
+
+    /// Read the PEB pointer stored in the current thread's TEB, which a
+    /// segment register addresses (`gs:[0x60]` on x64, `fs:[0x30]` on x86).
+    #[cfg(target_arch = "x86_64")]
+    unsafe fn peb() -> *const u8 {
+        let peb: *const u8;
+        core::arch::asm!("mov {}, gs:[0x60]", out(reg) peb);
+        peb
+    }
+
+    #[cfg(target_arch = "x86")]
+    unsafe fn peb() -> *const u8 {
+        let peb: *const u8;
+        core::arch::asm!("mov {}, fs:[0x30]", out(reg) peb);
+        peb
+    }
+
+    #[cfg(any(target_arch = "x86_64", target_arch = "x86"))]
+    pub(super) fn is_debugger_present_thorough() -> DebuggerPresenceSignals {
+        let mut signals = DebuggerPresenceSignals::default();
+
+        // SAFETY: `peb()` returns the current thread's PEB, and both
+        // offsets read here are within it and stable across the Windows
+        // versions this crate supports.
+        unsafe {
+            let peb = peb();
+            signals.being_debugged = *peb.add(BEING_DEBUGGED_OFFSET) != 0;
+
+            let nt_global_flag = *(peb.add(NT_GLOBAL_FLAG_OFFSET) as *const u32);
+            signals.heap_debug_flags = (nt_global_flag & HEAP_DEBUG_FLAGS) != 0;
+        }
+
+        // SAFETY: `GetCurrentProcess` returns a pseudo-handle that does not
+        // need closing, and `present` is a valid stack location for the
+        // call to write into.
+        unsafe {
+            let mut present = 0;
+            CheckRemoteDebuggerPresent(GetCurrentProcess(), &mut present);
+            signals.check_remote_debugger_present = present != 0;
+        }
+
+        signals
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+mod os {
+    use super::{DebuggerPresence, DebuggerPresenceError};
+    use libc::{c_int, c_void, sysctl, CTL_KERN, KERN_PROC, KERN_PROC_PID};
+    use std::{mem::size_of_val, process};
+
+    #[cfg(target_os = "macos")]
+    mod traced {
+        const P_TRACED: i32 = 0x00000800;
+
+        // The structure definition contain nested structure and plethora
+        // of fields that are not interesting here. Combine all the unnecessary
+        // fields into one array. `libc` doesn't appear to have the definition
+        // at the moment.
+        #[repr(C)]
+        pub(super) struct KinfoProc {
+            _unused0: [u8; 32],
+            p_flag: i32,
+            _unused1: [u8; 612],
+        }
+
+        impl KinfoProc {
+            pub(super) fn is_traced(&self) -> bool {
+                (self.p_flag & P_TRACED) != 0
+            }
+        }
+    }
+
+    #[cfg(target_os = "freebsd")]
+    mod traced {
+        const P_TRACED: i32 = 0x00000002;
+
+        #[repr(C)]
+        pub(super) struct KinfoProc {
+            _ki_structsize: i32, // Size of the structure
+            ki_flag: i32,        // Process flags (important for P_TRACED)
+            _ki_pid: i32,        // Process ID (useful for identification)
+            _ki_ppid: i32,       // Parent process ID
+            _ki_tid: i32,        // Thread ID
+            _ki_traced: u8,      // Tracing status (indicates if being traced)
+            _unused: [u8; 496],  // Combine all unnecessary fields here
+        }
+
+        impl KinfoProc {
+            pub(super) fn is_traced(&self) -> bool {
+                (self.ki_flag & P_TRACED) != 0
+            }
+        }
+    }
+
+    pub(super) fn is_debugger_present() -> Result<DebuggerPresence, DebuggerPresenceError> {
+        let mut info = unsafe { std::mem::zeroed::<traced::KinfoProc>() };
+        let mut mib = [CTL_KERN, KERN_PROC, KERN_PROC_PID, process::id() as c_int];
+        let mut info_size = size_of_val(&info);
+
+        // SAFETY: No state is shared with other threads. The sysctl call
+        // is safe according to the documentation.
+        if unsafe {
+            sysctl(
+                mib.as_mut_ptr(),
+                mib.len() as u32,
+                &mut info as *mut _ as *mut c_void,
+                &mut info_size,
+                std::ptr::null_mut(),
+                0,
+            )
+        } == 0
+        {
+            if info.is_traced() {
+                Ok(DebuggerPresence::Detected)
+            } else {
+                Ok(DebuggerPresence::NotDetected)
+            }
+        } else {
+            Err(DebuggerPresenceError::DetectionFailed)
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod os {
+    use super::{DebuggerPresence, DebuggerPresenceError};
+    use std::{
+        fs::File,
+        io::{BufRead, BufReader},
+    };
+
+    pub(super) fn is_debugger_present() -> Result<DebuggerPresence, DebuggerPresenceError> {
+        let file =
+            File::open("/proc/self/status").map_err(|_| DebuggerPresenceError::NotImplemented)?;
+        let reader = BufReader::new(file);
+
+        for line in reader.lines().flatten() {
+            if line.starts_with("TracerPid:") {
+                return if line
+                    .split(':')
+                    .nth(1)
+                    .map_or(false, |pid| pid.trim() != "0")
+                {
+                    Ok(DebuggerPresence::Detected)
+                } else {
+                    Ok(DebuggerPresence::NotDetected)
+                };
+            }
+        }
+
+        Err(DebuggerPresenceError::DetectionFailed)
+    }
+}
+
+#[cfg(not(any(
+    target_os = "windows",
+    target_os = "macos",
+    target_os = "freebsd",
+    target_os = "linux"
+)))]
+mod os {
+    use super::{DebuggerPresence, DebuggerPresenceError};
+
+    pub(super) fn is_debugger_present() -> Result<DebuggerPresence, DebuggerPresenceError> {
+        Err(DebuggerPresenceError::NotImplemented)
+    }
+}
+
+/// Detect the debugger presence.
+pub fn is_debugger_present() -> Result<DebuggerPresence, DebuggerPresenceError> {
+    os::is_debugger_present()
+}
+
+/// Detect the debugger presence the hard way, by cross-checking several
+/// sources instead of trusting `IsDebuggerPresent` alone.
+///
+/// Only available on `x86_64`/`x86`; see [`DebuggerPresenceSignals`].
+#[cfg(all(target_os = "windows", any(target_arch = "x86_64", target_arch = "x86")))]
+pub fn is_debugger_present_thorough() -> DebuggerPresenceSignals {
+    os::is_debugger_present_thorough()
+}
+
+/// Execute the breakpoint instruction if the debugger presence is detected.
+/// This is racy and does not try to detect the debugger at all costs (e.g.,
+/// when anti-debugger tricks are at play). Useful for breaking into the
+/// debugger without the need to set a breakpoint in the debugger.
+pub fn breakpoint_if_debugging() {
+    if let Ok(DebuggerPresence::Detected) = is_debugger_present() {
+        crate::breakpoint!();
+    }
+}
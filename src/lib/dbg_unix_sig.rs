@@ -0,0 +1,210 @@
+//! Signal-guarded breakpoint trap for Unix targets.
+//!
+//! Unlike Windows, where [`crate::breakpoint_if_debugging_seh`] relies on
+//! SEH to step over an unconsumed trap, Unix has no equivalent safety net:
+//! [`crate::breakpoint!`] executes `int3`/`brk`/`ebreak` unconditionally,
+//! and without an attached debugger that delivers `SIGTRAP` and aborts the
+//! process.
+//!
+//! `breakpoint_if_debugging_guarded` installs a `SIGTRAP` handler, once,
+//! with `sigaction`, so that execution resumes past the trap instruction:
+//! on `aarch64` the handler advances the saved PC in the `ucontext_t`, on
+//! `x86_64` the kernel already reports it past `int3`. If a real debugger is attached
+//! it intercepts the `SIGTRAP` as a first-chance exception before our
+//! handler ever runs, so the handler not firing is itself the signal that
+//! a debugger is present.
+//!
+//! The handler is installed once and kept for the life of the process
+//! rather than swapped in and out around every call: a per-call
+//! install/restore would race with another thread trapping concurrently.
+//! [`BreakpointGuard`] is what lets the handler tell "our" trap apart from
+//! a foreign one (a real debugger-set breakpoint, or a crash) on a given
+//! thread, including when calls nest.
+//!
+//! Resuming past the trap instruction depends on where the architecture
+//! leaves the saved program counter in the `ucontext_t`, which this module
+//! only handles for `x86_64` and `aarch64`; other Unix architectures don't get
+//! this module, just the unguarded [`crate::breakpoint!`].
+
+#![cfg(all(unix, any(target_arch = "x86_64", target_arch = "aarch64")))]
+
+use crate::DebuggerPresence;
+use std::cell::{Cell, RefCell};
+use std::os::raw::{c_int, c_void};
+use std::sync::Once;
+
+thread_local! {
+    // One entry per `BreakpointGuard` currently alive on this thread, in
+    // nesting order. The handler marks the innermost (last) entry when it
+    // steps over a trap raised while that guard was active.
+    static TRAP_GUARDS: RefCell<Vec<Cell<bool>>> = const { RefCell::new(Vec::new()) };
+}
+
+static INSTALL: Once = Once::new();
+static mut PREVIOUS_ACTION: libc::sigaction = unsafe { std::mem::zeroed() };
+
+/// Marks, for the lifetime of the value, that the current thread is about
+/// to execute an intentional trap.
+///
+/// The installed `SIGTRAP` handler only steps over a trap raised while a
+/// guard is active on the trapping thread; any other `SIGTRAP` (a real
+/// debugger-set breakpoint, or a crash) is passed through to whatever
+/// handler was installed before ours. Guards nest: a guard constructed
+/// while another is already active on the same thread doesn't disturb it,
+/// so `breakpoint_if_debugging_guarded` stays safe to call reentrantly and
+/// from multiple threads at once.
+///
+/// Nested guards on the same thread must be dropped in the LIFO order
+/// they were created in, exactly like any other stack-allocated RAII
+/// guard (`drop` checks this in debug builds). Don't stash one in a place
+/// that would let it outlive a guard created after it, and don't
+/// `mem::forget` one.
+pub struct BreakpointGuard {
+    depth: usize,
+}
+
+impl BreakpointGuard {
+    /// Mark the current thread as about to execute an intentional trap.
+    pub fn new() -> Self {
+        install_handler();
+
+        let depth = TRAP_GUARDS.with(|guards| {
+            let mut guards = guards.borrow_mut();
+            guards.push(Cell::new(false));
+            guards.len()
+        });
+
+        BreakpointGuard { depth }
+    }
+
+    /// Whether a trap raised while this guard was active got stepped over
+    /// by our handler. `false` means either no trap has happened yet, or a
+    /// debugger intercepted it before our handler ran.
+    pub fn stepped_over(&self) -> bool {
+        TRAP_GUARDS.with(|guards| {
+            guards
+                .borrow()
+                .get(self.depth - 1)
+                .is_some_and(Cell::get)
+        })
+    }
+}
+
+impl Default for BreakpointGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for BreakpointGuard {
+    /// Guards must be dropped in the strict LIFO order they were created
+    /// in (as any stack-allocated RAII guard would be) — dropping an outer
+    /// guard before an inner one, or `mem::forget`-ing one, would truncate
+    /// the per-thread stack out from under a still-alive guard and
+    /// silently corrupt `stepped_over()` for it. Flag the invariant in
+    /// debug builds. In release builds, only truncate when this guard is
+    /// indeed the innermost one still on the stack; dropped out of order,
+    /// truncating would remove entries belonging to guards that are still
+    /// alive, so instead leave the stack untouched and let those guards
+    /// clean up their own slot when they are eventually dropped.
+    fn drop(&mut self) {
+        TRAP_GUARDS.with(|guards| {
+            let mut guards = guards.borrow_mut();
+            debug_assert_eq!(
+                guards.len(),
+                self.depth,
+                "BreakpointGuard dropped out of LIFO order"
+            );
+            if guards.len() == self.depth {
+                guards.truncate(self.depth - 1);
+            }
+        });
+    }
+}
+
+/// Execute the breakpoint instruction, stepping over it if no debugger
+/// consumes the resulting `SIGTRAP`.
+///
+/// Returns `Some(DebuggerPresence::Detected)` if a debugger intercepted
+/// the trap, and `Some(DebuggerPresence::NotDetected)` if our handler had
+/// to step over it itself.
+pub fn breakpoint_if_debugging_guarded() -> Option<DebuggerPresence> {
+    let guard = BreakpointGuard::new();
+    crate::breakpoint!();
+
+    if guard.stepped_over() {
+        Some(DebuggerPresence::NotDetected)
+    } else {
+        Some(DebuggerPresence::Detected)
+    }
+}
+
+fn install_handler() {
+    INSTALL.call_once(|| {
+        // SAFETY: `PREVIOUS_ACTION` is only ever touched here, behind
+        // `Once`, and by `handle_sigtrap`, which never writes to it.
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = handle_sigtrap as *const () as usize;
+            action.sa_flags = libc::SA_SIGINFO;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(libc::SIGTRAP, &action, std::ptr::addr_of_mut!(PREVIOUS_ACTION));
+        }
+    });
+}
+
+extern "C" fn handle_sigtrap(signum: c_int, info: *mut libc::siginfo_t, context: *mut c_void) {
+    let ours = TRAP_GUARDS.with(|guards| {
+        let guards = guards.borrow();
+        if let Some(innermost) = guards.last() {
+            innermost.set(true);
+            true
+        } else {
+            false
+        }
+    });
+
+    if !ours {
+        // No guard is active on this thread: not a trap we raised, so
+        // chain to whatever handler was installed before us instead of
+        // silently swallowing it.
+        // SAFETY: `PREVIOUS_ACTION` was populated by `install_handler`
+        // before any trap could reach this handler.
+        unsafe {
+            let previous = std::ptr::addr_of!(PREVIOUS_ACTION.sa_sigaction).read();
+            if previous == libc::SIG_IGN {
+                // The previous disposition was to ignore SIGTRAP: honor it.
+            } else if previous == libc::SIG_DFL {
+                // The previous disposition was the default one, which for
+                // SIGTRAP is to terminate the process. Restore it and
+                // re-raise so the signal actually takes effect, instead of
+                // our handler returning and the trap being swallowed.
+                let mut action: libc::sigaction = std::mem::zeroed();
+                action.sa_sigaction = libc::SIG_DFL;
+                libc::sigemptyset(&mut action.sa_mask);
+                libc::sigaction(libc::SIGTRAP, &action, std::ptr::null_mut());
+                libc::raise(libc::SIGTRAP);
+            } else {
+                let handler: extern "C" fn(c_int, *mut libc::siginfo_t, *mut c_void) =
+                    std::mem::transmute(previous);
+                handler(signum, info, context);
+            }
+        }
+        return;
+    }
+
+    // `int3` is a trap, not a fault: the kernel already reports RIP past
+    // the single-byte `0xCC` at signal delivery, so x86_64 has nothing to
+    // step over here. `brk` on aarch64 is reported at-instruction, so the
+    // saved PC does need to be advanced past it.
+    #[cfg(target_arch = "aarch64")]
+    // SAFETY: `context` is the `ucontext_t*` the kernel passes to a
+    // `SA_SIGINFO` handler for the thread that raised `signum`.
+    unsafe {
+        let ctx = &mut *(context as *mut libc::ucontext_t);
+        ctx.uc_mcontext.pc += 4;
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    let _ = context;
+}
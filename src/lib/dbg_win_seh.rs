@@ -13,8 +13,11 @@
 //! }
 //! ```
 //!
-//! The implementation is available for the `x86_64` and the `aarch64`
-//! targets under Windows.
+//! The implementation is available for the `x86_64`, `aarch64` and `x86`
+//! targets under Windows. The `x86` path uses classic frame-based SEH,
+//! chained through `fs:[0]` in the TIB, rather than the table-based
+//! `.seh_*` directives the other two targets rely on (untested: no
+//! 32-bit Windows box handy to check it on).
 //!
 //! To learn more about SEH attributes empirically, can compile C files to
 //! assembly with `clang` or `cl`. As for the documentation, here are some
@@ -127,8 +130,87 @@ core::arch::global_asm!(
 pub fn breakpoint_if_debugging_seh() -> Option<DebuggerPresence> {
     // SAFETY: the call does not access any state shared between threads.
     match unsafe { __dbg_breakpoint() } {
-        0 => Some(DebuggerPresence::NotDetected),
         -1 => Some(DebuggerPresence::Detected),
-        _ => panic!("Internal error"),
+        // `__dbg_breakpoint` only ever returns `0` or `-1`; treat anything
+        // else the same as `0` rather than panicking. This can run from
+        // inside the hook `install_panic_hook` installs, where panicking
+        // again would abort the process and lose the original panic.
+        _ => Some(DebuggerPresence::NotDetected),
+    }
+}
+
+// 32-bit Windows has no `.seh_*` table-based unwind info, so there is no
+// way to express this with the directives used above. Instead, push a
+// classic `EXCEPTION_REGISTRATION_RECORD` onto the stack and link it into
+// the SEH chain through `fs:[0]`, the way hand-written frame-based SEH
+// always has on x86.
+#[cfg(target_arch = "x86")]
+#[link(name = "ntdll")]
+extern "system" {
+    fn RtlUnwind(
+        target_frame: *mut core::ffi::c_void,
+        target_ip: *mut core::ffi::c_void,
+        exception_record: *mut core::ffi::c_void,
+        return_value: *mut core::ffi::c_void,
+    );
+}
+
+#[cfg(target_arch = "x86")]
+extern "C" {
+    /// Breakpoint that is passed to the debugger as the first chance exception
+    /// if the debugger is attached, and is skipped over otherwise.
+    /// Returns `0` if no debugger was sensed, and `-1` if it was.
+    fn __dbg_breakpoint() -> i32;
+}
+
+#[cfg(target_arch = "x86")]
+core::arch::global_asm!(
+    r#"
+        .pushsection    .text
+
+        .globl          __dbg_breakpoint_handler
+        .p2align        4
+    __dbg_breakpoint_handler:
+        # SEH frame handler, called as:
+        #   handler(PEXCEPTION_RECORD, EstablisherFrame, PCONTEXT, PDISPATCHER_CONTEXT)
+        # Resume at the label that reports "no debugger", past the `int3`,
+        # with every other register as it was at the trap.
+        mov             eax, [esp + 12]            # PCONTEXT
+        mov             dword ptr [eax + 0xb8], offset __dbg_breakpoint_handled # CONTEXT.Eip
+        xor             eax, eax                   # ExceptionContinueExecution
+        ret
+
+        .globl          __dbg_breakpoint
+        .p2align        4
+    __dbg_breakpoint:
+        push            offset __dbg_breakpoint_handler
+        push            dword ptr fs:[0]
+        mov             dword ptr fs:[0], esp
+    1:
+        int3
+        mov             eax, -1
+        jmp             2f
+    __dbg_breakpoint_handled:
+        xor             eax, eax
+    2:
+        mov             ecx, dword ptr [esp]       # previous fs:[0] link, still on the stack
+        mov             fs:[0], ecx
+        add             esp, 8
+        ret
+        .text
+        .popsection
+    "#
+);
+
+#[cfg(target_arch = "x86")]
+pub fn breakpoint_if_debugging_seh() -> Option<DebuggerPresence> {
+    // SAFETY: the call does not access any state shared between threads.
+    match unsafe { __dbg_breakpoint() } {
+        -1 => Some(DebuggerPresence::Detected),
+        // `__dbg_breakpoint` only ever returns `0` or `-1`; treat anything
+        // else the same as `0` rather than panicking. This can run from
+        // inside the hook `install_panic_hook` installs, where panicking
+        // again would abort the process and lose the original panic.
+        _ => Some(DebuggerPresence::NotDetected),
     }
 }
@@ -1,6 +1,9 @@
 mod dbg;
 mod dbg_win_seh;
 
+#[cfg(all(unix, any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod dbg_unix_sig;
+
 /// Execute the breakpoint instruction. That might crash the program if the debugger/tracer
 /// is not able to step over the instruction.
 #[macro_export]
@@ -53,6 +56,58 @@ pub enum DebuggerPresence {
 pub use dbg::breakpoint_if_debugging;
 pub use dbg::is_debugger_present;
 
-// Don't have a 32-bit Windows around, might try a VM.
-#[cfg(all(target_os = "windows", target_pointer_width = "64"))]
+#[cfg(target_os = "windows")]
 pub use dbg_win_seh::breakpoint_if_debugging_seh;
+
+#[cfg(all(target_os = "windows", any(target_arch = "x86_64", target_arch = "x86")))]
+pub use dbg::is_debugger_present_thorough;
+#[cfg(all(target_os = "windows", any(target_arch = "x86_64", target_arch = "x86")))]
+pub use dbg::DebuggerPresenceSignals;
+
+#[cfg(all(unix, any(target_arch = "x86_64", target_arch = "aarch64")))]
+pub use dbg_unix_sig::breakpoint_if_debugging_guarded;
+#[cfg(all(unix, any(target_arch = "x86_64", target_arch = "aarch64")))]
+pub use dbg_unix_sig::BreakpointGuard;
+
+/// Trap into the attached debugger, if any.
+///
+/// Uses whichever skippable trap is available for the current target —
+/// the SEH-guarded trap on Windows, the signal-guarded trap on `x86_64`/
+/// `aarch64` Unix — falling back to the bare [`breakpoint!`] everywhere
+/// else, which is only safe to reach when a debugger is known to be
+/// present.
+pub fn breakpoint_on_panic() {
+    if let Ok(DebuggerPresence::Detected) = is_debugger_present() {
+        #[cfg(target_os = "windows")]
+        {
+            breakpoint_if_debugging_seh();
+        }
+
+        #[cfg(all(unix, any(target_arch = "x86_64", target_arch = "aarch64")))]
+        {
+            breakpoint_if_debugging_guarded();
+        }
+
+        #[cfg(not(any(
+            target_os = "windows",
+            all(unix, any(target_arch = "x86_64", target_arch = "aarch64"))
+        )))]
+        {
+            breakpoint!();
+        }
+    }
+}
+
+/// Install a panic hook that breaks into the attached debugger, if any,
+/// before running the previously installed hook.
+///
+/// This lets a developer inspect live state at the panic site instead of
+/// only seeing the unwound backtrace. If no debugger is attached, the
+/// previous hook runs unchanged.
+pub fn install_panic_hook() {
+    let previous_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        breakpoint_on_panic();
+        previous_hook(info);
+    }));
+}